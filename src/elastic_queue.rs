@@ -0,0 +1,150 @@
+/// Copyright (c) Peter Sanders. All rights reserved.
+/// Date: 2018-02-01
+///
+/// An internal growable buffer that lets `Scanner` look arbitrarily far
+/// ahead into its underlying stream. A single `BufRead::fill_buf()` window
+/// is bounded by the reader's own buffer size (64KB for a default
+/// `BufReader`), which is too small whenever a token or delimiter straddles
+/// that boundary. `ElasticQueue` repeatedly drains the stream into a
+/// `Vec<u8>` so callers can keep growing the window until they have enough
+/// bytes to make a decision, with no upper bound on how far a single read
+/// can grow it. Bytes are reclaimed again once nothing (no unconsumed data,
+/// no outstanding `Mark`) still needs them, so steady-state memory use
+/// tracks the live lookahead rather than the whole stream.
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::BufRead;
+use std::rc::Rc;
+
+/// How far `cursor` is allowed to drift past the oldest byte we could
+/// still discard before we bother draining the front of `buf`. Keeps
+/// `compact` from doing an O(n) shift on every single-byte consume.
+const COMPACT_THRESHOLD: usize = 8192;
+
+/// Offsets pinned by a live `Mark`, keyed by offset with a reference count
+/// (several marks can share the same offset). The lowest key is the
+/// earliest point `compact` is allowed to discard up to.
+type MarkRegistry = Rc<RefCell<BTreeMap<usize, usize>>>;
+
+/// A growable byte buffer paired with a cursor marking how much of it has
+/// already been consumed by the scanner.
+pub(crate) struct ElasticQueue {
+    buf: Vec<u8>,
+    // Absolute offset of `buf[0]` in the overall stream.
+    base: usize,
+    // Absolute offset of the next unconsumed byte.
+    cursor: usize,
+    marks: MarkRegistry,
+}
+
+/// A handle pinning the byte at `offset` so `compact` won't discard it.
+/// Releases the pin when dropped.
+pub(crate) struct MarkGuard {
+    registry: MarkRegistry,
+    offset: usize,
+}
+
+impl Drop for MarkGuard {
+    fn drop(&mut self) {
+        let mut marks = self.registry.borrow_mut();
+        let drop_entry = match marks.get_mut(&self.offset) {
+            Some(count) => {
+                *count -= 1;
+                *count == 0
+            }
+            None => false,
+        };
+        if drop_entry {
+            marks.remove(&self.offset);
+        }
+    }
+}
+
+impl ElasticQueue {
+    pub(crate) fn new() -> ElasticQueue {
+        ElasticQueue {
+            buf: Vec::new(),
+            base: 0,
+            cursor: 0,
+            marks: Rc::new(RefCell::new(BTreeMap::new())),
+        }
+    }
+
+    /// Pulls another chunk of bytes from `stream` into the buffer. Returns
+    /// `Ok(true)` if any bytes were read, or `Ok(false)` at EOF.
+    pub(crate) fn fill<R: BufRead + ?Sized>(&mut self, stream: &mut R) -> io::Result<bool> {
+        let read = {
+            let available = stream.fill_buf()?;
+            if available.is_empty() {
+                return Ok(false);
+            }
+            self.buf.extend_from_slice(available);
+            available.len()
+        };
+        stream.consume(read);
+
+        Ok(true)
+    }
+
+    /// The bytes that have been read from the stream but not yet consumed.
+    pub(crate) fn available(&self) -> &[u8] {
+        &self.buf[self.cursor - self.base..]
+    }
+
+    /// Marks `count` bytes at the front of `available()` as consumed, then
+    /// reclaims any of the buffer's front that no longer needs to be kept
+    /// around for a `Mark` to rewind to.
+    pub(crate) fn consume(&mut self, count: usize) {
+        self.cursor += count;
+        self.compact();
+    }
+
+    /// Drops bytes before the oldest position anything still cares about,
+    /// once there are enough of them to make the shift worthwhile.
+    fn compact(&mut self) {
+        let floor = self
+            .marks
+            .borrow()
+            .keys()
+            .next()
+            .cloned()
+            .unwrap_or(self.cursor)
+            .min(self.cursor);
+
+        if floor - self.base < COMPACT_THRESHOLD {
+            return;
+        }
+
+        self.buf.drain(..floor - self.base);
+        self.base = floor;
+    }
+
+    /// The absolute offset of the next unconsumed byte; used as a `Mark`.
+    pub(crate) fn position(&self) -> usize {
+        self.cursor
+    }
+
+    /// Pins the given offset so `compact` cannot discard it until the
+    /// returned guard is dropped.
+    pub(crate) fn pin(&self, offset: usize) -> MarkGuard {
+        *self.marks.borrow_mut().entry(offset).or_insert(0) += 1;
+
+        MarkGuard {
+            registry: Rc::clone(&self.marks),
+            offset,
+        }
+    }
+
+    /// Rewinds the cursor to a previously captured offset without touching
+    /// the underlying stream. Returns `false` if `offset` names bytes that
+    /// are no longer buffered.
+    pub(crate) fn reset(&mut self, offset: usize) -> bool {
+        if offset < self.base || offset > self.base + self.buf.len() {
+            return false;
+        }
+        self.cursor = offset;
+
+        true
+    }
+}
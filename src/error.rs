@@ -0,0 +1,52 @@
+/// Copyright (c) Peter Sanders. All rights reserved.
+/// Date: 2018-02-01
+///
+/// Error type returned by the `try_*` family of `Scanner` methods, which
+/// distinguish clean end-of-input from I/O failures, invalid UTF-8, and
+/// failed parses -- outcomes the `Option`-returning methods collapse into
+/// a single `None`.
+use std::error;
+use std::fmt;
+use std::io;
+
+/// The ways a `Scanner` read can fail.
+#[derive(Debug)]
+pub enum ScannerError {
+    /// The stream was exhausted before a token could be read.
+    UnexpectedEof,
+    /// The underlying stream returned an I/O error.
+    Io(io::Error),
+    /// The bytes read were not valid UTF-8.
+    InvalidUtf8,
+    /// A token was read successfully but could not be parsed as the
+    /// requested type (including overflow).
+    ParseFailure,
+}
+
+impl fmt::Display for ScannerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ScannerError::UnexpectedEof => write!(f, "unexpected end of input"),
+            ScannerError::Io(ref e) => write!(f, "I/O error: {}", e),
+            ScannerError::InvalidUtf8 => write!(f, "stream did not contain valid UTF-8"),
+            ScannerError::ParseFailure => write!(f, "token could not be parsed"),
+        }
+    }
+}
+
+impl error::Error for ScannerError {
+    fn description(&self) -> &str {
+        match *self {
+            ScannerError::UnexpectedEof => "unexpected end of input",
+            ScannerError::Io(_) => "I/O error",
+            ScannerError::InvalidUtf8 => "invalid UTF-8",
+            ScannerError::ParseFailure => "parse failure",
+        }
+    }
+}
+
+impl From<io::Error> for ScannerError {
+    fn from(e: io::Error) -> ScannerError {
+        ScannerError::Io(e)
+    }
+}
@@ -3,20 +3,83 @@
 extern crate regex;
 
 use std::io::BufRead;
+use std::num::{ParseFloatError, ParseIntError};
 use std::str;
 use regex::Regex; // For regex "delim"
 
+mod elastic_queue;
+mod error;
+mod position;
 #[cfg(test)]
 mod tests;
+mod token;
+
+use elastic_queue::{ElasticQueue, MarkGuard};
+pub use error::ScannerError;
+pub use position::Position;
+pub use token::{Token, TokenKind};
+
+/// The number of bytes a UTF-8 sequence occupies, as determined by its
+/// leading byte. Returns 1 for an invalid leading byte, so a malformed
+/// sequence is treated one byte at a time rather than desynchronizing the
+/// whole stream.
+fn utf8_width(lead: u8) -> usize {
+    if lead & 0x80 == 0x00 {
+        1
+    } else if lead & 0xE0 == 0xC0 {
+        2
+    } else if lead & 0xF0 == 0xE0 {
+        3
+    } else if lead & 0xF8 == 0xF0 {
+        4
+    } else {
+        1
+    }
+}
+
+/// Whether `s` looks like it was meant to be a number, regardless of
+/// whether it actually parses -- i.e. it is made up of digits, at most
+/// one decimal point, and an optional leading sign.
+fn looks_like_number(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .enumerate()
+            .all(|(i, c)| c.is_ascii_digit() || c == '.' || ((c == '-' || c == '+') && i == 0))
+        && s.chars().any(|c| c.is_ascii_digit())
+        && s.matches('.').count() <= 1
+}
+
+/// A saved scanner position, taken with `Scanner::mark` and later restored
+/// with `Scanner::reset` to support speculative, backtrackable reads.
+pub struct Mark {
+    offset: usize,
+    delim: Regex,
+    position: Position,
+    line_starts: usize, // length of `line_starts` at the time of the mark
+    bom_checked: bool,  // whether the BOM check had already run
+    // Pins `offset` in the elastic buffer so it survives compaction for as
+    // long as this `Mark` is alive.
+    _guard: MarkGuard,
+}
 
 /// Rust implementation of java.util.Scanner
 pub struct Scanner<'a> {
-    stream: &'a mut BufRead, // Underlying stream object we are handling
+    stream: &'a mut dyn BufRead, // Underlying stream object we are handling
     delim: Regex,            // Delimiter used to specify word boundaries
+    queue: ElasticQueue,     // Elastic lookahead buffer drawn from `stream`
+    byte_pos: usize,         // Total bytes consumed so far
+    line: usize,             // One-based line of the next unconsumed byte
+    column: usize,           // One-based column of the next unconsumed byte
+    line_starts: Vec<usize>, // Byte offset that each line begins at
+    skip_bom: bool,          // Whether to strip a leading UTF-8 BOM
+    bom_checked: bool,       // Whether we've already looked for a BOM
 }
 
+/// The three bytes of a leading UTF-8 byte-order mark.
+const BOM: [u8; 3] = [0xEF, 0xBB, 0xBF];
+
 /// Implements the meta-methods of Scanner that affect how the data stream
-/// is processed, e.g., delimiter, parsing radix, etc.
+/// is processed, e.g., delimiter, BOM handling, etc.
 impl<'a> Scanner<'a> {
     pub fn set_delim(&mut self, delim: Regex) -> &Regex {
         self.delim = delim;
@@ -33,109 +96,442 @@ impl<'a> Scanner<'a> {
     pub fn get_delim(&self) -> &Regex {
         &self.delim
     }
+    /// Sets whether a leading UTF-8 byte-order mark should be stripped
+    /// before the first token is read. Defaults to `true`.
+    pub fn set_skip_bom(&mut self, skip_bom: bool) {
+        self.skip_bom = skip_bom;
+    }
+    pub fn get_skip_bom(&self) -> bool {
+        self.skip_bom
+    }
 }
 
 /// Implements the methods of Scanner that affect the underlying data stream
 impl<'a> Scanner<'a> {
     /// Creates a new instance of Scanner
-    pub fn new(stream: &'a mut BufRead) -> Scanner {
+    pub fn new(stream: &'a mut dyn BufRead) -> Scanner<'a> {
         Scanner {
-            stream: stream,
+            stream,
             // We can safely unwrap this regex because it is hard-coded.
             delim: Regex::new(r"\s+").unwrap(),
+            queue: ElasticQueue::new(),
+            byte_pos: 0,
+            line: 1,
+            column: 1,
+            line_starts: vec![0],
+            skip_bom: true,
+            bom_checked: false,
+        }
+    }
+
+    /// Strips a leading UTF-8 byte-order mark on the very first read, if
+    /// `skip_bom` is set. A no-op on every call after the first, unless
+    /// `reset` has rewound to a `Mark` taken before that first call.
+    fn strip_bom(&mut self) -> Result<(), ScannerError> {
+        if self.bom_checked {
+            return Ok(());
+        }
+        self.bom_checked = true;
+
+        if !self.skip_bom {
+            return Ok(());
+        }
+
+        while self.queue.available().len() < BOM.len() {
+            if !self.queue.fill(self.stream)? {
+                break;
+            }
+        }
+
+        if self.queue.available().starts_with(&BOM) {
+            self.consume(BOM.len());
+        }
+
+        Ok(())
+    }
+
+    /// Reports where the next unconsumed byte sits in the stream, so a
+    /// parser built on top of `Scanner` can produce "line N, column M"
+    /// diagnostics for a token it just read.
+    pub fn position(&self) -> Position {
+        Position {
+            byte: self.byte_pos,
+            line: self.line,
+            column: self.column,
         }
     }
 
+    /// Advances the position bookkeeping over `count` bytes taken from the
+    /// front of `self.queue.available()`, then consumes them.
+    fn consume(&mut self, count: usize) {
+        for &b in &self.queue.available()[..count] {
+            self.byte_pos += 1;
+            if b == b'\n' {
+                self.line += 1;
+                self.column = 1;
+                self.line_starts.push(self.byte_pos);
+            } else {
+                self.column += 1;
+            }
+        }
+        self.queue.consume(count);
+    }
+
     /// Returns Some(String) containing the next string if there is one.
-    /// Otherwise returns None.
+    /// Otherwise returns None. See `try_next` for a version that
+    /// distinguishes *why* there was no token.
+    // Named to mirror java.util.Scanner#next, not std::iter::Iterator::next
+    // -- Scanner isn't, and isn't meant to become, an Iterator.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<String> {
+        self.try_next().ok()
+    }
+
+    /// Attempts to retrieve the next whitespace- (or `delim`-) separated
+    /// token.
     ///
     /// We first consume all leading `delim`s, then attempt to read everything
     /// until (but excluding) the next `delim`. If this results in an empty
-    /// string, we will return `None`.
-    pub fn next(&mut self) -> Option<String> {
-        let mut consume_counter = 0;
-        let mut res = String::new();
-
-        consume_counter = {
-            if let Ok(buf) = self.stream.fill_buf() {
-                // If the buffer is not a valid utf-8 string, we exit the
-                // method with `None` result.
-                if str::from_utf8(buf).is_err() {
-                    return None;
-                }
+    /// string, we return `ScannerError::UnexpectedEof`.
+    ///
+    /// The search is performed against an internal elastic buffer rather
+    /// than a single `fill_buf()` window, so a delimiter (or the token
+    /// before it) may be arbitrarily longer than the stream's own buffer.
+    /// A delimiter match that touches the tail of what we've buffered so
+    /// far is only *possibly* partial -- the real delimiter could extend
+    /// past it -- so we keep reading until either the match is no longer
+    /// at the tail or the stream is exhausted.
+    ///
+    /// On `ScannerError::InvalidUtf8`, the offending bytes are still
+    /// consumed before returning, so a caller that keeps calling `try_next`
+    /// after an error makes forward progress instead of seeing the same
+    /// invalid bytes forever.
+    pub fn try_next(&mut self) -> Result<String, ScannerError> {
+        self.strip_bom()?;
 
-                // The check above guarantees `unwrap` will succeed.
-                let mut input: &str = str::from_utf8(buf).unwrap();
+        loop {
+            // `is_tail` means the delimiter match (or lack of one) reaches
+            // the end of what we've buffered so far, so it might still be
+            // a prefix of a longer delimiter once we read more.
+            let (is_tail, consumed, res) = match str::from_utf8(self.queue.available()) {
+                Ok(input) => {
+                    // While the front of the buffer matches `delim`, skip it.
+                    let mut skip = 0;
+                    while let Some(found) = self.delim.find(&input[skip..]) {
+                        if found.start() > 0 {
+                            break;
+                        }
+                        skip += found.end();
+                    }
+                    let remaining = &input[skip..];
 
-                // While the front of the buffer matches `delim`, skip it.
-                while let Some(found) = self.delim.find(input) {
-                    if found.start() > 0 {
-                        break;
+                    match self.delim.find(remaining) {
+                        Some(found) => (
+                            found.end() == remaining.len(),
+                            skip + found.start(),
+                            String::from(&remaining[..found.start()]),
+                        ),
+                        None => (true, skip + remaining.len(), String::from(remaining)),
                     }
-                    consume_counter += found.end();
-                    input = &input[found.end()..];
                 }
+                // `Utf8Error` doesn't borrow `self.queue`, so it's safe to
+                // hold across the `consume`/`fill` calls below.
+                Err(e) => match e.error_len() {
+                    // Genuinely invalid bytes, as opposed to a sequence
+                    // truncated at the tail of what we've buffered so far --
+                    // advance past them so a caller looping on
+                    // `Err(InvalidUtf8)` makes forward progress instead of
+                    // reading the same bytes forever.
+                    Some(bad_len) => {
+                        self.consume(e.valid_up_to() + bad_len);
+                        return Err(ScannerError::InvalidUtf8);
+                    }
+                    None => {
+                        if self.queue.fill(self.stream)? {
+                            continue;
+                        }
+                        // Truncated at true EOF; it can never become valid.
+                        let len = self.queue.available().len();
+                        self.consume(len);
+                        return Err(ScannerError::InvalidUtf8);
+                    }
+                },
+            };
 
-                if let Some(found) = self.delim.find(input) {
-                    res = String::from(&input[..found.start()]);
+            if is_tail && self.queue.fill(self.stream)? {
+                continue;
+            }
 
-                    consume_counter + found.start()
-                } else {
-                    res = String::from(input);
+            self.consume(consumed);
 
-                    consume_counter + input.len()
-                }
+            return if !res.is_empty() {
+                Ok(res)
             } else {
-                0
-            }
-        };
-        self.stream.consume(consume_counter);
-
-        if res.len() > 0 {
-            Some(res)
-        } else {
-            None
+                Err(ScannerError::UnexpectedEof)
+            };
         }
     }
 
     /// Read up to the next NEW_LINE character. If there are any leading `delim`s,
     /// they will be included in the returned string.
     pub fn next_line(&mut self) -> Option<String> {
-        let mut res = String::new();
+        self.try_next_line().ok()
+    }
 
-        if let Ok(_size) = self.stream.read_line(&mut res) {
-            if let Some(end) = res.pop() {
-                if end == '\n' {
-                    Some(res)
-                } else {
-                    res.push(end);
+    /// Like `next_line`, but distinguishes clean end-of-input from I/O and
+    /// UTF-8 failures.
+    ///
+    /// On `ScannerError::InvalidUtf8`, the offending bytes are still
+    /// consumed before returning, so a caller that keeps calling
+    /// `try_next_line` after an error makes forward progress instead of
+    /// seeing the same invalid bytes forever.
+    pub fn try_next_line(&mut self) -> Result<String, ScannerError> {
+        self.strip_bom()?;
 
-                    Some(res)
+        loop {
+            let (is_tail, consumed, res) = match str::from_utf8(self.queue.available()) {
+                Ok(input) => {
+                    if let Some(idx) = input.find('\n') {
+                        (false, idx + 1, String::from(&input[..idx]))
+                    } else {
+                        (true, input.len(), String::from(input))
+                    }
                 }
+                // `Utf8Error` doesn't borrow `self.queue`, so it's safe to
+                // hold across the `consume`/`fill` calls below.
+                Err(e) => match e.error_len() {
+                    // Genuinely invalid bytes -- advance past them so a
+                    // caller looping on `Err(InvalidUtf8)` makes forward
+                    // progress instead of reading the same bytes forever.
+                    Some(bad_len) => {
+                        self.consume(e.valid_up_to() + bad_len);
+                        return Err(ScannerError::InvalidUtf8);
+                    }
+                    // A sequence truncated at the tail of what we've
+                    // buffered so far, not necessarily invalid -- pull more
+                    // data and give it a chance to complete before giving
+                    // up, just like `try_next` does for a `delim` match
+                    // that reaches the tail.
+                    None => {
+                        if self.queue.fill(self.stream)? {
+                            continue;
+                        }
+                        // Truncated at true EOF; it can never become valid.
+                        let len = self.queue.available().len();
+                        self.consume(len);
+                        return Err(ScannerError::InvalidUtf8);
+                    }
+                },
+            };
+
+            if is_tail && self.queue.fill(self.stream)? {
+                continue;
+            }
+
+            self.consume(consumed);
+
+            return if is_tail && res.is_empty() {
+                Err(ScannerError::UnexpectedEof)
             } else {
-                None
+                Ok(res)
+            };
+        }
+    }
+
+    /// Reads exactly one Unicode scalar value from the stream, independent
+    /// of `delim`. Returns `None` at true end-of-input or on an I/O
+    /// error (see `try_next_char` to tell them apart); a truncated
+    /// or malformed UTF-8 sequence yields the replacement character
+    /// `U+FFFD` instead of failing the read.
+    pub fn next_char(&mut self) -> Option<char> {
+        self.try_next_char().ok()
+    }
+
+    /// Like `next_char`, but distinguishes true end-of-input from an I/O
+    /// error. A truncated or malformed sequence still yields `U+FFFD`
+    /// rather than an error, since the stream itself is not at fault.
+    pub fn try_next_char(&mut self) -> Result<char, ScannerError> {
+        self.strip_bom()?;
+
+        while self.queue.available().is_empty() {
+            if !self.queue.fill(self.stream)? {
+                return Err(ScannerError::UnexpectedEof);
             }
-        } else {
-            None
         }
+
+        let width = utf8_width(self.queue.available()[0]);
+        while self.queue.available().len() < width {
+            if !self.queue.fill(self.stream)? {
+                // The sequence is truncated at EOF; take what we have.
+                break;
+            }
+        }
+
+        let take = width.min(self.queue.available().len());
+        let ch = str::from_utf8(&self.queue.available()[..take])
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or('\u{FFFD}');
+        self.consume(take);
+
+        Ok(ch)
+    }
+
+    /// Captures the scanner's current position so a later call to `reset`
+    /// can rewind back to it, enabling speculative, backtrackable reads.
+    pub fn mark(&self) -> Mark {
+        let offset = self.queue.position();
+
+        Mark {
+            offset,
+            delim: self.delim.clone(),
+            position: self.position(),
+            line_starts: self.line_starts.len(),
+            bom_checked: self.bom_checked,
+            _guard: self.queue.pin(offset),
+        }
+    }
+
+    /// Rewinds the scanner to a previously captured `Mark`, without
+    /// touching the underlying stream. Returns `None` if the marked bytes
+    /// are no longer buffered.
+    pub fn reset(&mut self, mark: Mark) -> Option<()> {
+        if !self.queue.reset(mark.offset) {
+            return None;
+        }
+        self.delim = mark.delim;
+        self.byte_pos = mark.position.byte;
+        self.line = mark.position.line;
+        self.column = mark.position.column;
+        self.line_starts.truncate(mark.line_starts);
+        self.bom_checked = mark.bom_checked;
+
+        Some(())
+    }
+
+    /// Returns the next token without consuming it, so a subsequent call
+    /// to `next` (or any other read) will see it again.
+    pub fn peek(&mut self) -> Option<String> {
+        let mark = self.mark();
+        let res = self.next();
+        self.reset(mark);
+
+        res
+    }
+
+    /// Returns the next line without consuming it, so a subsequent call
+    /// to `next_line` (or any other read) will see it again.
+    pub fn peek_line(&mut self) -> Option<String> {
+        let mark = self.mark();
+        let res = self.next_line();
+        self.reset(mark);
+
+        res
     }
 
     /// Attempts to retrieve the next 32-bit unsigned integer.
     /// Even if this fails, we still consume the `next` item.
     pub fn next_i32(&mut self) -> Option<i32> {
-        if let Some(mut input) = self.next() {
-            // Strip commas. Numbers with commas are considered valid
-            // but Rust does not recognize them in its default behavior.
-            while let Some(comma_idx) = input.rfind(',') {
-                input.remove(comma_idx);
+        self.try_next_int::<i32>().ok()
+    }
+
+    /// Attempts to retrieve the next integer, of any type parseable from a
+    /// decimal string. Even if this fails, we still consume the `next`
+    /// item. Distinguishes a missing token from one that overflowed `T` or
+    /// otherwise failed to parse.
+    pub fn try_next_int<T>(&mut self) -> Result<T, ScannerError>
+    where
+        T: str::FromStr<Err = ParseIntError>,
+    {
+        let mut input = self.try_next()?;
+
+        // Strip commas. Numbers with commas are considered valid
+        // but Rust does not recognize them in its default behavior.
+        while let Some(comma_idx) = input.rfind(',') {
+            input.remove(comma_idx);
+        }
+
+        input.parse::<T>().map_err(|_e| ScannerError::ParseFailure)
+    }
+
+    /// Attempts to retrieve the next floating-point number. Even if this
+    /// fails, we still consume the `next` item.
+    pub fn next_float<T>(&mut self) -> Option<T>
+    where
+        T: str::FromStr<Err = ParseFloatError>,
+    {
+        self.try_next_float::<T>().ok()
+    }
+
+    /// Like `next_float`, but distinguishes a missing token from one that
+    /// failed to parse.
+    pub fn try_next_float<T>(&mut self) -> Result<T, ScannerError>
+    where
+        T: str::FromStr<Err = ParseFloatError>,
+    {
+        let input = self.try_next()?;
+
+        input.parse::<T>().map_err(|_e| ScannerError::ParseFailure)
+    }
+
+    /// Reads the next `delim`-separated chunk and classifies it as a
+    /// `Token`, instead of giving up and returning `None` the way
+    /// `next_i32`/`next_float` do. Like `next`, the result is
+    /// delimiter-stripped, so `TokenKind` has no `Delimiter` variant to
+    /// classify into. A chunk that looks numeric but overflows or otherwise
+    /// fails to parse is still returned, with `malformed` set, so the
+    /// caller never loses the span. Returns `None` only at true
+    /// end-of-input.
+    pub fn next_token(&mut self) -> Option<Token> {
+        let text = match self.try_next() {
+            Ok(text) => text,
+            Err(ScannerError::UnexpectedEof) => return None,
+            Err(_) => {
+                return Some(Token {
+                    kind: TokenKind::Unknown,
+                    text: String::new(),
+                    malformed: true,
+                })
             }
+        };
 
-            match input.parse::<i32>() {
-                Ok(res) => Some(res),
-                Err(_e) => None,
+        // Strip commas before checking whether this looks numeric, just
+        // like `next_i32` does.
+        let mut stripped = text.clone();
+        while let Some(comma_idx) = stripped.rfind(',') {
+            stripped.remove(comma_idx);
+        }
+
+        if looks_like_number(&stripped) {
+            if stripped.contains('.') {
+                let malformed = stripped.parse::<f64>().is_err();
+                return Some(Token {
+                    kind: TokenKind::Float,
+                    text,
+                    malformed,
+                });
             }
-        } else {
-            None
+
+            let malformed = stripped.parse::<i64>().is_err();
+            return Some(Token {
+                kind: TokenKind::Integer,
+                text,
+                malformed,
+            });
         }
+
+        if text.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Some(Token {
+                kind: TokenKind::Word,
+                text,
+                malformed: false,
+            });
+        }
+
+        Some(Token {
+            kind: TokenKind::Unknown,
+            text,
+            malformed: false,
+        })
     }
 }
@@ -0,0 +1,19 @@
+//! Copyright (c) Peter Sanders. All rights reserved.
+//! Date: 2018-02-01
+//!
+//! Diagnostic position reporting for `Scanner`. Borrows the `BytePos` /
+//! line-table model used by the rustc lexer: we track a running byte
+//! offset as data is consumed, plus a table of line-start offsets, so a
+//! caller building a parser on top of `Scanner` can report "line N,
+//! column M" for any token it reads.
+
+/// A single point in the input stream, as of the last byte consumed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    /// Zero-based offset from the start of the stream, in bytes.
+    pub byte: usize,
+    /// One-based line number.
+    pub line: usize,
+    /// One-based column, in bytes, within `line`.
+    pub column: usize,
+}
@@ -3,6 +3,7 @@
 ///
 /// Unit tests for Rust implementation of Scanner.
 use super::*;
+use std::io;
 
 #[test]
 fn next_works_once_when_good_input() {
@@ -112,7 +113,7 @@ fn next_int_handles_commas() {
     let mut string: &[u8] = b"2,147,483,647";
     let mut test: Scanner = Scanner::new(&mut string);
 
-    assert_eq!(test.next_int::<i32>(), Some(2147483647));
+    assert_eq!(test.next_i32(), Some(2147483647));
 }
 
 #[test]
@@ -120,7 +121,7 @@ fn next_int_none_on_positive_overflow() {
     let mut string: &[u8] = b"2147483648";
     let mut test: Scanner = Scanner::new(&mut string);
 
-    let res = test.next_int::<i32>();
+    let res = test.next_i32();
     assert_eq!(res, None);
 }
 
@@ -129,7 +130,7 @@ fn next_i32_none_on_negative_overflow() {
     let mut string: &[u8] = b"-2147483649";
     let mut test: Scanner = Scanner::new(&mut string);
 
-    let res = test.next_int::<i32>();
+    let res = test.next_i32();
     assert_eq!(res, None);
 }
 
@@ -155,53 +156,225 @@ fn next_float() {
 }
 
 #[test]
-fn next_int_custom_radix() {
-    let mut string: &[u8] = b"11010";
-    let mut test = Scanner::new(&mut string);
+fn str_delim_escapes_regexes() {
+    let mut string: &[u8] = b"foo[a-z]+bar";
+    let mut test: Scanner = Scanner::new(&mut string);
+    test.set_delim_str("[a-z]+");
+
+    test.next();
+    if let Some(res) = test.next() {
+        assert_eq!(&res[..], "bar");
+    } else {
+        assert_eq!(true, false);
+    }
+}
+
+/// A `BufRead` that hands back one byte per `fill_buf` call, regardless of
+/// how much is actually available, so a test can force the scanner's
+/// elastic buffer to grow across several reads instead of getting
+/// everything from a single `fill_buf` window like a plain `&[u8]` does.
+struct OneByteAtATime<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> io::Read for OneByteAtATime<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = buf.len().min(self.data.len() - self.pos);
+        buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+        self.pos += n;
 
-    // invalid radix should return None and not consume `Scanner.next()`
-    assert_eq!(test.next_int_radix::<i32>(1), None);
+        Ok(n)
+    }
+}
+
+impl<'a> io::BufRead for OneByteAtATime<'a> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let end = (self.pos + 1).min(self.data.len());
+
+        Ok(&self.data[self.pos..end])
+    }
 
-    // 2 is a valid radix.
-    assert_eq!(test.next_int_radix(2), Some(26));
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt;
+    }
 }
 
 #[test]
-fn next_float_base_2() {
-    let mut string: &[u8] = b"11010.1";
-    let mut test = Scanner::new(&mut string);
+fn next_assembles_token_spanning_many_fill_calls() {
+    let data = b"a-very-long-token-indeed, short";
+    let mut stream = OneByteAtATime { data, pos: 0 };
+    let mut test: Scanner = Scanner::new(&mut stream);
 
-    // invalid radix should return None and not consume `Scanner.next()`
-    assert_eq!(test.next_float_radix::<f64>(1), None);
+    if let Some(res) = test.next() {
+        assert_eq!(&res[..], "a-very-long-token-indeed,");
+    } else {
+        assert_eq!(true, false);
+    }
+}
 
-    // 2 is a valid radix.
-    assert_eq!(test.next_float_radix(2), Some(26.5));
+#[test]
+fn next_line_assembles_multibyte_scalar_split_across_fill_calls() {
+    let data = "café\nx".as_bytes();
+    let mut stream = OneByteAtATime { data, pos: 0 };
+    let mut test: Scanner = Scanner::new(&mut stream);
+
+    if let Some(res) = test.next_line() {
+        assert_eq!(&res[..], "café");
+    } else {
+        assert_eq!(true, false);
+    }
 }
-    
+
 #[test]
-fn str_delim_escapes_regexes() {
-    let mut string: &[u8] = b"foo[a-z]+bar";
+fn mark_reset_rewinds_to_saved_position() {
+    let mut string: &[u8] = b"hello world";
     let mut test: Scanner = Scanner::new(&mut string);
-    test.set_delim_str("[a-z]+");
 
+    let mark = test.mark();
     test.next();
+    test.reset(mark);
+
     if let Some(res) = test.next() {
-        assert_eq!(&res[..], "bar");
+        assert_eq!(&res[..], "hello");
     } else {
         assert_eq!(true, false);
     }
 }
 
 #[test]
-fn radix_between_2_36() {
+fn peek_does_not_consume() {
+    let mut string: &[u8] = b"hello world";
+    let mut test: Scanner = Scanner::new(&mut string);
+
+    assert_eq!(test.peek(), Some(String::from("hello")));
+    assert_eq!(test.next(), Some(String::from("hello")));
+}
+
+#[test]
+fn try_next_reports_eof_when_only_delims_remain() {
+    let mut string: &[u8] = b"   ";
+    let mut test: Scanner = Scanner::new(&mut string);
+
+    match test.try_next() {
+        Err(ScannerError::UnexpectedEof) => {}
+        other => panic!("expected UnexpectedEof, got {:?}", other),
+    }
+}
+
+#[test]
+fn try_next_int_reports_parse_failure() {
+    let mut string: &[u8] = b"not-a-number";
+    let mut test: Scanner = Scanner::new(&mut string);
+
+    match test.try_next_int::<i32>() {
+        Err(ScannerError::ParseFailure) => {}
+        other => panic!("expected ParseFailure, got {:?}", other),
+    }
+}
+
+#[test]
+fn next_char_reads_one_scalar_at_a_time() {
+    let mut string: &[u8] = "héllo".as_bytes();
+    let mut test: Scanner = Scanner::new(&mut string);
+
+    assert_eq!(test.next_char(), Some('h'));
+    assert_eq!(test.next_char(), Some('é'));
+    assert_eq!(test.next_char(), Some('l'));
+}
+
+#[test]
+fn next_char_none_at_eof() {
     let mut string: &[u8] = b"";
-    let mut test = Scanner::new(&mut string);
+    let mut test: Scanner = Scanner::new(&mut string);
+
+    assert_eq!(test.next_char(), None);
+}
+
+#[test]
+fn position_tracks_line_and_column_across_newlines() {
+    let mut string: &[u8] = b"foo\nbar";
+    let mut test: Scanner = Scanner::new(&mut string);
+
+    test.next_line();
+    let pos = test.position();
+    assert_eq!(pos.line, 2);
+    assert_eq!(pos.column, 1);
+
+    test.next();
+    let pos = test.position();
+    assert_eq!(pos.line, 2);
+    assert_eq!(pos.column, 4);
+}
+
+#[test]
+fn next_token_classifies_integer() {
+    let mut string: &[u8] = b"42 foo";
+    let mut test: Scanner = Scanner::new(&mut string);
+
+    let token = test.next_token().unwrap();
+    assert_eq!(token.kind, TokenKind::Integer);
+    assert_eq!(&token.text[..], "42");
+    assert!(!token.malformed);
+}
+
+#[test]
+fn next_token_flags_overflowed_integer_as_malformed() {
+    let mut string: &[u8] = b"99999999999999999999999999";
+    let mut test: Scanner = Scanner::new(&mut string);
 
-    assert_eq!(test.get_radix(), 10);
-    test.set_radix(1);
-    assert_eq!(test.get_radix(), 10);
-    test.set_radix(37);
-    assert_eq!(test.get_radix(), 10);
-    test.set_radix(36);
-    assert_eq!(test.get_radix(), 36);
+    let token = test.next_token().unwrap();
+    assert_eq!(token.kind, TokenKind::Integer);
+    assert!(token.malformed);
+}
+
+#[test]
+fn try_next_line_advances_past_invalid_utf8_instead_of_looping() {
+    let mut bytes: &[u8] = &[0xFF, b'a', b'b', b'c', b'\n'];
+    let mut test: Scanner = Scanner::new(&mut bytes);
+
+    match test.try_next_line() {
+        Err(ScannerError::InvalidUtf8) => {}
+        other => panic!("expected InvalidUtf8, got {:?}", other),
+    }
+
+    if let Some(res) = test.next_line() {
+        assert_eq!(&res[..], "abc");
+    } else {
+        assert_eq!(true, false);
+    }
+}
+
+#[test]
+fn next_token_advances_past_invalid_utf8_instead_of_looping() {
+    let mut bytes: &[u8] = &[0xFF, b'a', b'b', b'c'];
+    let mut test: Scanner = Scanner::new(&mut bytes);
+
+    let bad = test.next_token().unwrap();
+    assert_eq!(bad.kind, TokenKind::Unknown);
+    assert!(bad.malformed);
+
+    let good = test.next_token().unwrap();
+    assert_eq!(&good.text[..], "abc");
+}
+
+#[test]
+fn strips_leading_bom_by_default() {
+    let mut bytes: &[u8] = b"\xEF\xBB\xBFhello";
+    let mut test: Scanner = Scanner::new(&mut bytes);
+
+    assert_eq!(test.next(), Some(String::from("hello")));
+}
+
+#[test]
+fn keeps_leading_bom_when_disabled() {
+    let mut bytes: &[u8] = b"\xEF\xBB\xBFhello";
+    let mut test: Scanner = Scanner::new(&mut bytes);
+    test.set_skip_bom(false);
+
+    if let Some(res) = test.next() {
+        assert!(res.starts_with('\u{FEFF}'));
+    } else {
+        assert_eq!(true, false);
+    }
 }
@@ -0,0 +1,31 @@
+//! Copyright (c) Peter Sanders. All rights reserved.
+//! Date: 2018-02-01
+//!
+//! Token types produced by `Scanner::next_token`, a tokenizing mode
+//! inspired by `rustc_lexer`: rather than aborting on the first bad
+//! input, it classifies each chunk and records problems as a flag on the
+//! token instead of discarding it.
+
+/// The broad category a `Token` falls into. `next_token` is
+/// delimiter-stripped like `Scanner::next`, so a `Token`'s `text` never
+/// consists of a delimiter run -- there is no `Delimiter` kind to classify
+/// into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Integer,
+    Float,
+    Word,
+    Unknown,
+}
+
+/// A classified chunk of input. `malformed` is set when `kind` is
+/// `Integer` or `Float` but the text failed to parse (e.g. on overflow),
+/// so the caller still gets the span instead of losing it to a silent
+/// `None`. It is also set on an `Unknown` token with empty `text` when
+/// the chunk could not be read at all (invalid UTF-8 or an I/O error).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+    pub malformed: bool,
+}